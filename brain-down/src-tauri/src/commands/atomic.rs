@@ -0,0 +1,41 @@
+// =============================================================================
+// Atomic File Writes
+// Crash-safe temp-file-and-rename writes, shared by the file and config
+// commands so a power loss or crash mid-write can never corrupt a note or
+// wipe the config.
+// =============================================================================
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Write `bytes` to `path` atomically: write to a sibling temp file, flush
+/// and fsync it, then rename over the destination. Rename within a
+/// directory is atomic on all major platforms, so readers never observe a
+/// half-written file. On any error the temp file is removed and the
+/// original is left untouched.
+pub fn atomic_write(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let temp_path = temp_path_for(path);
+
+    let result = (|| -> Result<(), String> {
+        let mut temp_file = File::create(&temp_path)
+            .map_err(|e| format!("Failed to create temp file: {}", e))?;
+        temp_file
+            .write_all(bytes)
+            .map_err(|e| format!("Failed to write temp file: {}", e))?;
+        temp_file
+            .sync_all()
+            .map_err(|e| format!("Failed to flush temp file: {}", e))?;
+        fs::rename(&temp_path, path).map_err(|e| format!("Failed to finalize write: {}", e))
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+fn temp_path_for(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.tmp", path.to_string_lossy()))
+}
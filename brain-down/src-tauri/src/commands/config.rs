@@ -3,9 +3,10 @@
 // Rust backend for application configuration persistence
 // =============================================================================
 
+use crate::commands::atomic::atomic_write;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tauri::Manager;
 
 // -----------------------------------------------------------------------------
@@ -22,6 +23,10 @@ pub struct VaultEntry {
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AppConfig {
     pub vaults: Vec<VaultEntry>,
+    /// Escape hatch for power users: when true, file commands skip the
+    /// vault confinement guard and accept any path. Defaults to false.
+    #[serde(default)]
+    pub allow_unrestricted_paths: bool,
 }
 
 // -----------------------------------------------------------------------------
@@ -32,36 +37,32 @@ pub struct AppConfig {
 /// Returns default config if file doesn't exist.
 #[tauri::command]
 pub fn load_config(app_handle: tauri::AppHandle) -> Result<AppConfig, String> {
-    let config_path = get_config_file_path(&app_handle)?;
-    
-    if !config_path.exists() {
-        return Ok(AppConfig::default());
-    }
-    
-    let content = fs::read_to_string(&config_path)
-        .map_err(|e| format!("Failed to read config file: {}", e))?;
-    
-    serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse config file: {}", e))
+    load_config_from_disk(&app_handle)
 }
 
 /// Save the application config to disk.
-/// Creates the config directory if it doesn't exist.
+/// Creates the config directory if it doesn't exist. The write is atomic
+/// (temp file + rename) and the previous good config is rotated to a
+/// `.bak` file first, so a crash mid-write can never wipe the config.
 #[tauri::command]
 pub fn save_config(app_handle: tauri::AppHandle, config: AppConfig) -> Result<(), String> {
     let config_path = get_config_file_path(&app_handle)?;
-    
+
     // Ensure parent directory exists
     if let Some(parent) = config_path.parent() {
         fs::create_dir_all(parent)
             .map_err(|e| format!("Failed to create config directory: {}", e))?;
     }
-    
+
+    if config_path.exists() {
+        fs::copy(&config_path, backup_path_for(&config_path))
+            .map_err(|e| format!("Failed to back up previous config: {}", e))?;
+    }
+
     let content = serde_json::to_string_pretty(&config)
         .map_err(|e| format!("Failed to serialize config: {}", e))?;
-    
-    fs::write(&config_path, content)
-        .map_err(|e| format!("Failed to write config file: {}", e))
+
+    atomic_write(&config_path, content.as_bytes())
 }
 
 /// Check if a vault path is accessible (exists, is a directory, and is readable).
@@ -87,10 +88,149 @@ pub fn check_vault_accessible(path: &str) -> Result<bool, String> {
     }
 }
 
+// -----------------------------------------------------------------------------
+// Access Scope Guard
+// -----------------------------------------------------------------------------
+
+/// Canonicalize `path`, then verify it resolves inside one of the vault
+/// roots registered in `AppConfig.vaults`. Inspired by Tauri's
+/// capability/permission model: the commands that list, read, write,
+/// create, delete or rename vault files, and the encrypted-vault commands,
+/// route their incoming path through this guard instead of trusting it
+/// outright. `file_exists` and `check_vault_accessible` do not; they use the
+/// path as given. Set `AppConfig.allow_unrestricted_paths` to opt back into
+/// the legacy unrestricted behavior.
+pub fn resolve_within_vaults(app_handle: &tauri::AppHandle, path: &str) -> Result<PathBuf, String> {
+    let config = load_config_from_disk(app_handle)?;
+    let candidate = canonicalize_best_effort(Path::new(path))
+        .map_err(|e| format!("Failed to resolve path: {}", e))?;
+
+    if config.allow_unrestricted_paths {
+        return Ok(candidate);
+    }
+
+    for vault in &config.vaults {
+        if let Ok(root) = canonicalize_best_effort(Path::new(&vault.path)) {
+            if candidate.starts_with(&root) {
+                return Ok(candidate);
+            }
+        }
+    }
+
+    Err("path escapes allowed vaults".to_string())
+}
+
+/// Canonicalize `path`, following symlinks. If `path` itself doesn't exist
+/// yet (e.g. a file about to be created), canonicalize the nearest existing
+/// ancestor and rejoin the missing suffix, so still-to-be-created files can
+/// be confined just like existing ones.
+fn canonicalize_best_effort(path: &Path) -> std::io::Result<PathBuf> {
+    if let Ok(canonical) = path.canonicalize() {
+        return Ok(canonical);
+    }
+
+    let parent = path
+        .parent()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "path has no parent"))?;
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "path has no file name"))?;
+
+    let canonical_parent = canonicalize_best_effort(parent)?;
+    Ok(canonical_parent.join(file_name))
+}
+
+// -----------------------------------------------------------------------------
+// Platform Config Layering
+// -----------------------------------------------------------------------------
+
+/// Name of the sibling config file holding overrides for the current OS,
+/// chosen at compile time so a synced vault can carry per-platform absolute
+/// paths without the user editing `config.json` by hand.
+pub fn get_platform_config_filename() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "config.macos.json"
+    } else if cfg!(target_os = "windows") {
+        "config.windows.json"
+    } else {
+        "config.linux.json"
+    }
+}
+
+/// Apply an RFC 7396 JSON Merge Patch: `patch` is merged into `target` in
+/// place. A `null` member removes the corresponding key; an object member
+/// recurses; anything else (including arrays and scalars) replaces the
+/// target value wholesale.
+pub fn merge(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let patch_map = match patch.as_object() {
+        Some(map) => map,
+        None => {
+            *target = patch.clone();
+            return;
+        }
+    };
+
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    let target_map = target
+        .as_object_mut()
+        .expect("target was just coerced into an object");
+
+    for (key, patch_value) in patch_map {
+        if patch_value.is_null() {
+            target_map.remove(key);
+        } else {
+            let entry = target_map
+                .entry(key.clone())
+                .or_insert(serde_json::Value::Null);
+            merge(entry, patch_value);
+        }
+    }
+}
+
 // -----------------------------------------------------------------------------
 // Helper Functions
 // -----------------------------------------------------------------------------
 
+/// Read and parse the config file from disk, layering the platform-specific
+/// override file (if present) on top via JSON Merge Patch, and returning the
+/// default config if neither file exists. Shared by the `load_config`
+/// command and the access scope guard, which both need the current config
+/// outside of a frontend-initiated load.
+fn load_config_from_disk(app_handle: &tauri::AppHandle) -> Result<AppConfig, String> {
+    let config_path = get_config_file_path(app_handle)?;
+
+    let mut config_value = if config_path.exists() {
+        let content = fs::read_to_string(&config_path)
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse config file: {}", e))?
+    } else {
+        serde_json::to_value(AppConfig::default())
+            .map_err(|e| format!("Failed to build default config: {}", e))?
+    };
+
+    if let Some(parent) = config_path.parent() {
+        let platform_config_path = parent.join(get_platform_config_filename());
+        if platform_config_path.exists() {
+            let platform_content = fs::read_to_string(&platform_config_path)
+                .map_err(|e| format!("Failed to read platform config file: {}", e))?;
+            let patch: serde_json::Value = serde_json::from_str(&platform_content)
+                .map_err(|e| format!("Failed to parse platform config file: {}", e))?;
+            merge(&mut config_value, &patch);
+        }
+    }
+
+    serde_json::from_value(config_value).map_err(|e| format!("Failed to parse config file: {}", e))
+}
+
+/// Path to the rotated backup of the previous good config, kept so a
+/// corrupt or unwanted save can be recovered from.
+fn backup_path_for(config_path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.bak", config_path.to_string_lossy()))
+}
+
 /// Get the path to the config file.
 /// Located at: <app_data_dir>/config.json
 fn get_config_file_path(app_handle: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
@@ -98,6 +238,88 @@ fn get_config_file_path(app_handle: &tauri::AppHandle) -> Result<std::path::Path
         .path()
         .app_data_dir()
         .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-    
+
     Ok(app_data_dir.join("config.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn get_platform_config_filename_matches_current_os() {
+        let expected = if cfg!(target_os = "macos") {
+            "config.macos.json"
+        } else if cfg!(target_os = "windows") {
+            "config.windows.json"
+        } else {
+            "config.linux.json"
+        };
+
+        assert_eq!(get_platform_config_filename(), expected);
+    }
+
+    #[test]
+    fn merge_null_member_removes_key() {
+        let mut target = json!({ "vaults": [], "allow_unrestricted_paths": true });
+        let patch = json!({ "allow_unrestricted_paths": null });
+
+        merge(&mut target, &patch);
+
+        assert_eq!(target, json!({ "vaults": [] }));
+    }
+
+    #[test]
+    fn merge_recurses_into_nested_objects() {
+        let mut target = json!({
+            "window": { "width": 800, "height": 600 },
+            "vaults": [],
+        });
+        let patch = json!({
+            "window": { "width": 1024 },
+        });
+
+        merge(&mut target, &patch);
+
+        assert_eq!(
+            target,
+            json!({
+                "window": { "width": 1024, "height": 600 },
+                "vaults": [],
+            })
+        );
+    }
+
+    #[test]
+    fn merge_replaces_arrays_and_scalars_wholesale() {
+        let mut target = json!({
+            "vaults": [{ "id": "a", "name": "A", "path": "/a" }],
+            "allow_unrestricted_paths": false,
+        });
+        let patch = json!({
+            "vaults": [{ "id": "b", "name": "B", "path": "/b" }],
+            "allow_unrestricted_paths": true,
+        });
+
+        merge(&mut target, &patch);
+
+        assert_eq!(
+            target,
+            json!({
+                "vaults": [{ "id": "b", "name": "B", "path": "/b" }],
+                "allow_unrestricted_paths": true,
+            })
+        );
+    }
+
+    #[test]
+    fn merge_non_object_patch_replaces_target_entirely() {
+        let mut target = json!({ "vaults": [] });
+        let patch = json!("reset");
+
+        merge(&mut target, &patch);
+
+        assert_eq!(target, json!("reset"));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,279 @@
+// =============================================================================
+// Vault Crypto
+// Password-derived encryption for locked vaults, modeled on the Ethereum
+// keystore ("ethstore") layout: a vault.json file holds the KDF parameters,
+// a cipher identifier, and a MAC over an encrypted "vault key" blob. Once
+// unwrapped, the vault key is used to encrypt/decrypt individual file bodies.
+// =============================================================================
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use pbkdf2::pbkdf2_hmac;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+
+pub const VAULT_METADATA_FILE: &str = "vault.json";
+
+const KDF_ITERATIONS: u32 = 600_000;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+// Domain-separated HKDF info strings used to split the single
+// PBKDF2-derived master key into independent cipher and MAC keys, so the
+// same bytes are never used for two different primitives.
+const HKDF_CIPHER_INFO: &[u8] = b"brain-down vault wrap cipher key v1";
+const HKDF_MAC_INFO: &[u8] = b"brain-down vault wrap mac key v1";
+
+/// KDF parameters needed to re-derive the same key from a passphrase.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub salt: String,
+    pub iterations: u32,
+}
+
+/// On-disk vault metadata, modeled on the ethstore keystore layout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultMetadata {
+    pub cipher: String,
+    pub kdf: String,
+    pub kdf_params: KdfParams,
+    pub cipher_nonce: String,
+    pub ciphertext: String,
+    pub mac: String,
+}
+
+/// A decrypted vault key, held in memory only while the vault is unlocked.
+#[derive(Clone)]
+pub struct VaultKey(pub [u8; KEY_LEN]);
+
+/// Path to the `vault.json` metadata file for a vault directory.
+pub fn vault_metadata_path(vault_dir: &Path) -> PathBuf {
+    vault_dir.join(VAULT_METADATA_FILE)
+}
+
+/// Whether a vault directory has been turned into an encrypted vault.
+pub fn is_encrypted_vault(vault_dir: &Path) -> bool {
+    vault_metadata_path(vault_dir).is_file()
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+/// The independent cipher and MAC keys used to wrap a vault key, both
+/// derived from the single PBKDF2 master key via HKDF with distinct info
+/// strings, so the AES-GCM wrap and the outer HMAC never share key material.
+struct WrappingKeys {
+    cipher_key: [u8; KEY_LEN],
+    mac_key: [u8; KEY_LEN],
+}
+
+fn split_wrapping_keys(master_key: &[u8]) -> Result<WrappingKeys, String> {
+    let hkdf = Hkdf::<Sha256>::new(None, master_key);
+
+    let mut cipher_key = [0u8; KEY_LEN];
+    hkdf.expand(HKDF_CIPHER_INFO, &mut cipher_key)
+        .map_err(|e| format!("Failed to derive cipher key: {}", e))?;
+
+    let mut mac_key = [0u8; KEY_LEN];
+    hkdf.expand(HKDF_MAC_INFO, &mut mac_key)
+        .map_err(|e| format!("Failed to derive mac key: {}", e))?;
+
+    Ok(WrappingKeys { cipher_key, mac_key })
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut buf = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut buf);
+    buf
+}
+
+fn compute_mac(derived_key: &[u8], data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(derived_key)
+        .map_err(|e| format!("Failed to initialize MAC: {}", e))?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Generate a fresh vault key, wrap it under a passphrase-derived key, and
+/// produce the `vault.json` metadata that can later unwrap it.
+pub fn create_vault_metadata(passphrase: &str) -> Result<(VaultMetadata, VaultKey), String> {
+    let mut vault_key = [0u8; KEY_LEN];
+    rand::thread_rng().fill_bytes(&mut vault_key);
+    let vault_key = VaultKey(vault_key);
+
+    let metadata = wrap_vault_key(&vault_key, passphrase)?;
+    Ok((metadata, vault_key))
+}
+
+/// Wrap an existing vault key under a (possibly new) passphrase, producing
+/// fresh `vault.json` metadata. Used both for initial vault creation and for
+/// passphrase changes, where the vault key itself must stay the same so
+/// already-encrypted files remain readable.
+pub fn wrap_vault_key(vault_key: &VaultKey, passphrase: &str) -> Result<VaultMetadata, String> {
+    let salt = random_bytes(SALT_LEN);
+    let master_key = derive_key(passphrase, &salt, KDF_ITERATIONS);
+    let keys = split_wrapping_keys(&master_key)?;
+
+    let nonce_bytes = random_bytes(NONCE_LEN);
+    let wrapping_cipher = Aes256Gcm::new_from_slice(&keys.cipher_key)
+        .map_err(|e| format!("Failed to initialize cipher: {}", e))?;
+    let nonce = Nonce::from_slice(&nonce_bytes);
+    let ciphertext = wrapping_cipher
+        .encrypt(nonce, vault_key.0.as_slice())
+        .map_err(|e| format!("Failed to wrap vault key: {}", e))?;
+
+    let mac = compute_mac(&keys.mac_key, &ciphertext)?;
+
+    Ok(VaultMetadata {
+        cipher: "aes-256-gcm".to_string(),
+        kdf: "pbkdf2-hmac-sha256".to_string(),
+        kdf_params: KdfParams {
+            salt: hex::encode(&salt),
+            iterations: KDF_ITERATIONS,
+        },
+        cipher_nonce: hex::encode(&nonce_bytes),
+        ciphertext: hex::encode(&ciphertext),
+        mac: hex::encode(&mac),
+    })
+}
+
+/// Re-derive the key from `passphrase` and unwrap the vault key, verifying
+/// the MAC first so a wrong passphrase fails cleanly instead of producing
+/// garbage key material.
+pub fn unwrap_vault_key(metadata: &VaultMetadata, passphrase: &str) -> Result<VaultKey, String> {
+    let salt = hex::decode(&metadata.kdf_params.salt)
+        .map_err(|_| "Corrupt vault metadata: bad salt".to_string())?;
+    let master_key = derive_key(passphrase, &salt, metadata.kdf_params.iterations);
+    let keys = split_wrapping_keys(&master_key)?;
+
+    let ciphertext = hex::decode(&metadata.ciphertext)
+        .map_err(|_| "Corrupt vault metadata: bad ciphertext".to_string())?;
+    let stored_mac = hex::decode(&metadata.mac)
+        .map_err(|_| "Corrupt vault metadata: bad mac".to_string())?;
+    let expected_mac = compute_mac(&keys.mac_key, &ciphertext)?;
+
+    if !constant_time_eq(&expected_mac, &stored_mac) {
+        return Err("Incorrect passphrase".to_string());
+    }
+
+    let nonce_bytes = hex::decode(&metadata.cipher_nonce)
+        .map_err(|_| "Corrupt vault metadata: bad nonce".to_string())?;
+    let cipher = Aes256Gcm::new_from_slice(&keys.cipher_key)
+        .map_err(|e| format!("Failed to initialize cipher: {}", e))?;
+    let nonce = Nonce::from_slice(&nonce_bytes);
+    let plaintext = cipher
+        .decrypt(nonce, ciphertext.as_slice())
+        .map_err(|_| "Incorrect passphrase".to_string())?;
+
+    if plaintext.len() != KEY_LEN {
+        return Err("Corrupt vault metadata: unexpected vault key length".to_string());
+    }
+    let mut vault_key = [0u8; KEY_LEN];
+    vault_key.copy_from_slice(&plaintext);
+    Ok(VaultKey(vault_key))
+}
+
+/// Encrypt a file body under the vault key. The nonce is stored as a
+/// fixed-size header so each file can be decrypted independently.
+pub fn encrypt_file_body(vault_key: &VaultKey, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let nonce_bytes = random_bytes(NONCE_LEN);
+    let cipher = Aes256Gcm::new_from_slice(&vault_key.0)
+        .map_err(|e| format!("Failed to initialize cipher: {}", e))?;
+    let nonce = Nonce::from_slice(&nonce_bytes);
+    let ciphertext = cipher
+        .encrypt(nonce, plaintext)
+        .map_err(|e| format!("Failed to encrypt file: {}", e))?;
+
+    let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(&nonce_bytes);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypt a file body previously produced by `encrypt_file_body`.
+pub fn decrypt_file_body(vault_key: &VaultKey, data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < NONCE_LEN {
+        return Err("Encrypted file is truncated".to_string());
+    }
+    let (nonce_bytes, ciphertext) = data.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new_from_slice(&vault_key.0)
+        .map_err(|e| format!("Failed to initialize cipher: {}", e))?;
+    let nonce = Nonce::from_slice(nonce_bytes);
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|_| "Failed to decrypt file: wrong vault key or corrupted data".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_and_unwrap_roundtrip_with_correct_passphrase() {
+        let vault_key = VaultKey([7u8; KEY_LEN]);
+
+        let metadata = wrap_vault_key(&vault_key, "correct horse battery staple").unwrap();
+        let unwrapped = unwrap_vault_key(&metadata, "correct horse battery staple").unwrap();
+
+        assert_eq!(unwrapped.0, vault_key.0);
+    }
+
+    #[test]
+    fn unwrap_with_wrong_passphrase_fails_the_mac_check() {
+        let vault_key = VaultKey([7u8; KEY_LEN]);
+        let metadata = wrap_vault_key(&vault_key, "correct horse battery staple").unwrap();
+
+        let result = unwrap_vault_key(&metadata, "wrong passphrase");
+
+        assert_eq!(result.unwrap_err(), "Incorrect passphrase");
+    }
+
+    #[test]
+    fn unwrap_with_tampered_ciphertext_fails_the_mac_check() {
+        let vault_key = VaultKey([7u8; KEY_LEN]);
+        let mut metadata = wrap_vault_key(&vault_key, "correct horse battery staple").unwrap();
+
+        let mut ciphertext = hex::decode(&metadata.ciphertext).unwrap();
+        ciphertext[0] ^= 0xFF;
+        metadata.ciphertext = hex::encode(&ciphertext);
+
+        let result = unwrap_vault_key(&metadata, "correct horse battery staple");
+
+        assert_eq!(result.unwrap_err(), "Incorrect passphrase");
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_file_body_roundtrip() {
+        let vault_key = VaultKey([42u8; KEY_LEN]);
+        let plaintext = b"at-rest encryption per vault";
+
+        let ciphertext = encrypt_file_body(&vault_key, plaintext).unwrap();
+        let decrypted = decrypt_file_body(&vault_key, &ciphertext).unwrap();
+
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn decrypt_file_body_with_wrong_key_fails() {
+        let vault_key = VaultKey([1u8; KEY_LEN]);
+        let other_key = VaultKey([2u8; KEY_LEN]);
+        let ciphertext = encrypt_file_body(&vault_key, b"secret note").unwrap();
+
+        assert!(decrypt_file_body(&other_key, &ciphertext).is_err());
+    }
+}
@@ -3,27 +3,71 @@
 // Rust backend for .mschema file operations
 // =============================================================================
 
+use crate::commands::atomic::atomic_write;
+use crate::commands::config::resolve_within_vaults;
+use crate::commands::crypto::{decrypt_file_body, encrypt_file_body, is_encrypted_vault};
+use crate::commands::vault::VaultKeyStore;
+use serde::Serialize;
 use std::fs;
 use std::path::Path;
 
-/// Read a .mschema file and return its content as a string
+/// Result of reading a `.mschema` file. `lossy` is set when the raw bytes
+/// weren't valid UTF-8 and had to be decoded with replacement characters, so
+/// the frontend can warn before an overwrite would destroy data.
+#[derive(Debug, Serialize)]
+pub struct MapFileContent {
+    pub content: String,
+    pub lossy: bool,
+    #[serde(rename = "detectedEncoding")]
+    pub detected_encoding: String,
+}
+
+/// Read a .mschema file and return its decoded content. If the file lives
+/// in an encrypted vault, it is transparently decrypted using the vault key
+/// held in app state; the plaintext never touches disk. Bytes that aren't
+/// valid UTF-8 (BOM, CRLF, Latin-1, ...) are decoded leniently rather than
+/// erroring out.
 #[tauri::command]
-pub fn read_map_file(path: &str) -> Result<String, String> {
-    let file_path = Path::new(path);
-    
+pub fn read_map_file(
+    app_handle: tauri::AppHandle,
+    path: &str,
+    state: tauri::State<VaultKeyStore>,
+) -> Result<MapFileContent, String> {
+    let file_path = resolve_within_vaults(&app_handle, path)?;
+    let file_path = file_path.as_path();
+
     if !file_path.exists() {
         return Err(format!("File does not exist: {}", path));
     }
-    
-    fs::read_to_string(file_path)
-        .map_err(|e| format!("Failed to read file: {}", e))
+
+    let bytes = fs::read(file_path).map_err(|e| format!("Failed to read file: {}", e))?;
+
+    let plaintext = match vault_key_for_file(file_path, &state)? {
+        Some(vault_key) => decrypt_file_body(&vault_key, &bytes)?,
+        None => bytes,
+    };
+
+    let (content, lossy, detected_encoding) = decode_lenient(plaintext);
+    Ok(MapFileContent {
+        content,
+        lossy,
+        detected_encoding: detected_encoding.to_string(),
+    })
 }
 
-/// Write content to a .mschema file
+/// Write content to a .mschema file. If the file lives in an encrypted
+/// vault, the content is transparently encrypted with the vault key held in
+/// app state before it is written.
 #[tauri::command]
-pub fn write_map_file(path: &str, content: &str) -> Result<(), String> {
-    let file_path = Path::new(path);
-    
+pub fn write_map_file(
+    app_handle: tauri::AppHandle,
+    path: &str,
+    content: &str,
+    state: tauri::State<VaultKeyStore>,
+) -> Result<(), String> {
+    let file_path = resolve_within_vaults(&app_handle, path)?;
+    let file_path = file_path.as_path();
+
     // Ensure parent directory exists
     if let Some(parent) = file_path.parent() {
         if !parent.exists() {
@@ -31,43 +75,59 @@ pub fn write_map_file(path: &str, content: &str) -> Result<(), String> {
                 .map_err(|e| format!("Failed to create directory: {}", e))?;
         }
     }
-    
-    fs::write(file_path, content)
-        .map_err(|e| format!("Failed to write file: {}", e))
+
+    let bytes = match vault_key_for_file(file_path, &state)? {
+        Some(vault_key) => encrypt_file_body(&vault_key, content.as_bytes())?,
+        None => content.as_bytes().to_vec(),
+    };
+
+    atomic_write(file_path, &bytes)
 }
 
 /// Create a new .mschema file in the vault
 /// Returns the path to the created file
 #[tauri::command]
-pub fn create_map_file(vault_path: &str, name: &str, content: &str) -> Result<String, String> {
-    let vault = Path::new(vault_path);
-    
+pub fn create_map_file(
+    app_handle: tauri::AppHandle,
+    vault_path: &str,
+    name: &str,
+    content: &str,
+    state: tauri::State<VaultKeyStore>,
+) -> Result<String, String> {
+    let vault = resolve_within_vaults(&app_handle, vault_path)?;
+    let vault = vault.as_path();
+
     if !vault.exists() || !vault.is_dir() {
         return Err(format!("Invalid vault path: {}", vault_path));
     }
-    
+
     // Sanitize the file name
     let safe_name = sanitize_filename(name);
     let file_name = format!("{}.mschema", safe_name);
     let file_path = vault.join(&file_name);
-    
+
     // Check if file already exists
     if file_path.exists() {
         return Err(format!("File already exists: {}", file_name));
     }
-    
-    // Write the file
-    fs::write(&file_path, content)
-        .map_err(|e| format!("Failed to create file: {}", e))?;
-    
+
+    let bytes = match vault_key_for_vault(vault, &state)? {
+        Some(vault_key) => encrypt_file_body(&vault_key, content.as_bytes())?,
+        None => content.as_bytes().to_vec(),
+    };
+
+    // Write the file atomically so a crash never leaves a partial file behind
+    atomic_write(&file_path, &bytes)?;
+
     Ok(file_path.to_string_lossy().to_string())
 }
 
 /// Delete a .mschema file
 #[tauri::command]
-pub fn delete_map_file(path: &str) -> Result<(), String> {
-    let file_path = Path::new(path);
-    
+pub fn delete_map_file(app_handle: tauri::AppHandle, path: &str) -> Result<(), String> {
+    let file_path = resolve_within_vaults(&app_handle, path)?;
+    let file_path = file_path.as_path();
+
     if !file_path.exists() {
         return Err(format!("File does not exist: {}", path));
     }
@@ -85,13 +145,24 @@ pub fn delete_map_file(path: &str) -> Result<(), String> {
 /// Rename a .mschema file
 /// Returns the new file path
 #[tauri::command]
-pub fn rename_map_file(old_path: &str, new_name: &str) -> Result<String, String> {
-    let old_file = Path::new(old_path);
-    
+pub fn rename_map_file(
+    app_handle: tauri::AppHandle,
+    old_path: &str,
+    new_name: &str,
+) -> Result<String, String> {
+    let old_file = resolve_within_vaults(&app_handle, old_path)?;
+    let old_file = old_file.as_path();
+
     if !old_file.exists() {
         return Err(format!("File does not exist: {}", old_path));
     }
-    
+
+    // Only allow renaming .mschema files for safety
+    match old_file.extension() {
+        Some(ext) if ext == "mschema" => {}
+        _ => return Err("Can only rename .mschema files".to_string()),
+    }
+
     let parent = old_file.parent()
         .ok_or_else(|| "Cannot get parent directory".to_string())?;
     
@@ -115,17 +186,180 @@ pub fn file_exists(path: &str) -> bool {
     Path::new(path).exists()
 }
 
+/// Look up the unwrapped vault key for the encrypted vault containing
+/// `file_path`, if any. Returns `Ok(None)` for files outside an encrypted
+/// vault, and an error if the vault is encrypted but still locked.
+fn vault_key_for_file(
+    file_path: &Path,
+    state: &tauri::State<VaultKeyStore>,
+) -> Result<Option<crate::commands::crypto::VaultKey>, String> {
+    let vault_dir = file_path
+        .parent()
+        .ok_or_else(|| "Cannot determine vault directory for file".to_string())?;
+    vault_key_for_vault(vault_dir, state)
+}
+
+/// Look up the unwrapped vault key for `vault_dir`, if it is an encrypted
+/// vault. Returns `Ok(None)` for unencrypted vaults, and an error if the
+/// vault is encrypted but still locked.
+fn vault_key_for_vault(
+    vault_dir: &Path,
+    state: &tauri::State<VaultKeyStore>,
+) -> Result<Option<crate::commands::crypto::VaultKey>, String> {
+    if !is_encrypted_vault(vault_dir) {
+        return Ok(None);
+    }
+
+    let canonical = vault_dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve vault path: {}", e))?
+        .to_string_lossy()
+        .to_string();
+
+    let keys = state
+        .0
+        .lock()
+        .map_err(|_| "Vault key store is poisoned".to_string())?;
+
+    keys.get(&canonical)
+        .cloned()
+        .map(Some)
+        .ok_or_else(|| "Vault is locked".to_string())
+}
+
+/// Decode raw file bytes leniently: strip a leading UTF-8/UTF-16 BOM if
+/// present, transcode UTF-16 content by code unit, otherwise attempt strict
+/// UTF-8, and on failure fall back to `String::from_utf8_lossy` so an
+/// externally-edited file (BOM, CRLF, Latin-1 content, ...) degrades
+/// gracefully instead of refusing to open. Returns the decoded content,
+/// whether the lossy fallback was used, and a label for the detected
+/// encoding.
+fn decode_lenient(bytes: Vec<u8>) -> (String, bool, &'static str) {
+    let (bytes, bom_encoding) = strip_bom(bytes);
+
+    match bom_encoding {
+        Some(encoding @ "utf-16le") => decode_utf16(&bytes, encoding, u16::from_le_bytes),
+        Some(encoding @ "utf-16be") => decode_utf16(&bytes, encoding, u16::from_be_bytes),
+        _ => match String::from_utf8(bytes) {
+            Ok(content) => (content, false, bom_encoding.unwrap_or("utf-8")),
+            Err(e) => {
+                let content = String::from_utf8_lossy(e.as_bytes()).into_owned();
+                (content, true, bom_encoding.unwrap_or("unknown"))
+            }
+        },
+    }
+}
+
+/// Decode raw UTF-16 bytes (after the BOM has been stripped) by pairing them
+/// into code units with `from_bytes` and transcoding via
+/// `String::from_utf16_lossy`. Flags `lossy` when the byte count is odd or
+/// any code unit required a replacement character.
+fn decode_utf16(bytes: &[u8], encoding: &'static str, from_bytes: fn([u8; 2]) -> u16) -> (String, bool, &'static str) {
+    let odd_trailing_byte = bytes.len() % 2 != 0;
+
+    let code_units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]))
+        .collect();
+
+    let lossless = String::from_utf16(&code_units);
+    let (content, well_formed) = match lossless {
+        Ok(content) => (content, true),
+        Err(_) => (String::from_utf16_lossy(&code_units), false),
+    };
+
+    (content, odd_trailing_byte || !well_formed, encoding)
+}
+
+/// Strip a leading UTF-8 or UTF-16 byte-order mark, returning the remaining
+/// bytes and a label for the encoding the BOM indicated, if any.
+fn strip_bom(bytes: Vec<u8>) -> (Vec<u8>, Option<&'static str>) {
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        (rest.to_vec(), Some("utf-8-bom"))
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        (rest.to_vec(), Some("utf-16le"))
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        (rest.to_vec(), Some("utf-16be"))
+    } else {
+        (bytes, None)
+    }
+}
+
 /// Sanitize a filename to remove potentially dangerous characters
 fn sanitize_filename(name: &str) -> String {
     name.chars()
         .filter(|c| {
-            c.is_alphanumeric() 
-            || *c == ' ' 
-            || *c == '-' 
-            || *c == '_' 
+            c.is_alphanumeric()
+            || *c == ' '
+            || *c == '-'
+            || *c == '_'
             || *c == '.'
         })
         .collect::<String>()
         .trim()
         .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_lenient_strips_utf8_bom() {
+        let mut bytes = vec![0xEF, 0xBB, 0xBF];
+        bytes.extend_from_slice("hello".as_bytes());
+
+        let (content, lossy, detected_encoding) = decode_lenient(bytes);
+
+        assert_eq!(content, "hello");
+        assert!(!lossy);
+        assert_eq!(detected_encoding, "utf-8-bom");
+    }
+
+    #[test]
+    fn decode_lenient_transcodes_well_formed_utf16le() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("Hi".encode_utf16().flat_map(|u| u.to_le_bytes()));
+
+        let (content, lossy, detected_encoding) = decode_lenient(bytes);
+
+        assert_eq!(content, "Hi");
+        assert!(!lossy);
+        assert_eq!(detected_encoding, "utf-16le");
+    }
+
+    #[test]
+    fn decode_lenient_transcodes_well_formed_utf16be() {
+        let mut bytes = vec![0xFE, 0xFF];
+        bytes.extend("Hi".encode_utf16().flat_map(|u| u.to_be_bytes()));
+
+        let (content, lossy, detected_encoding) = decode_lenient(bytes);
+
+        assert_eq!(content, "Hi");
+        assert!(!lossy);
+        assert_eq!(detected_encoding, "utf-16be");
+    }
+
+    #[test]
+    fn decode_lenient_flags_odd_trailing_byte_in_utf16_as_lossy() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("Hi".encode_utf16().flat_map(|u| u.to_le_bytes()));
+        bytes.push(0x41); // stray trailing byte, no matching pair
+
+        let (_content, lossy, detected_encoding) = decode_lenient(bytes);
+
+        assert!(lossy);
+        assert_eq!(detected_encoding, "utf-16le");
+    }
+
+    #[test]
+    fn decode_lenient_falls_back_to_lossy_for_invalid_utf8() {
+        let bytes = vec![b'h', b'i', 0xFF, 0xFE, 0x00];
+
+        let (content, lossy, detected_encoding) = decode_lenient(bytes);
+
+        assert!(lossy);
+        assert_eq!(detected_encoding, "unknown");
+        assert!(content.starts_with("hi"));
+    }
 }
\ No newline at end of file
@@ -6,6 +6,8 @@
 pub mod vault;
 pub mod file;
 pub mod config;
+pub(crate) mod atomic;
+pub(crate) mod crypto;
 
 // Re-export all commands for easy registration
 pub use vault::*;
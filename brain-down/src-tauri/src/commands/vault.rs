@@ -3,11 +3,25 @@
 // Rust backend for vault management
 // =============================================================================
 
+use crate::commands::atomic::atomic_write;
+use crate::commands::config::resolve_within_vaults;
+use crate::commands::crypto::{
+    self, create_vault_metadata, is_encrypted_vault, unwrap_vault_key, vault_metadata_path,
+    VaultKey, VaultMetadata,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::sync::Mutex;
 use tauri::Manager;
 
+/// Holds the unwrapped vault key for each unlocked encrypted vault, keyed by
+/// the vault's canonical directory path. Cleared when the app exits; a vault
+/// must be unlocked again every time the app starts.
+#[derive(Default)]
+pub struct VaultKeyStore(pub Mutex<HashMap<String, VaultKey>>);
+
 /// File entry returned to the frontend
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileEntry {
@@ -15,30 +29,40 @@ pub struct FileEntry {
     pub path: String,
     #[serde(rename = "modifiedAt")]
     pub modified_at: String,
+    /// True when this file belongs to an encrypted vault that hasn't been
+    /// unlocked yet; the frontend should not attempt to read its content.
+    pub locked: bool,
 }
 
 /// Get all .mschema files in the vault directory
 #[tauri::command]
-pub fn get_vault_files(vault_path: &str) -> Result<Vec<FileEntry>, String> {
-    let path = Path::new(vault_path);
-    
+pub fn get_vault_files(
+    app_handle: tauri::AppHandle,
+    vault_path: &str,
+    state: tauri::State<VaultKeyStore>,
+) -> Result<Vec<FileEntry>, String> {
+    let path = resolve_within_vaults(&app_handle, vault_path)?;
+    let path = path.as_path();
+
     if !path.exists() {
         return Err(format!("Vault path does not exist: {}", vault_path));
     }
-    
+
     if !path.is_dir() {
         return Err(format!("Vault path is not a directory: {}", vault_path));
     }
-    
+
+    let locked = is_encrypted_vault(path) && !is_vault_unlocked(path, &state);
+
     let mut files = Vec::new();
-    
+
     let entries = fs::read_dir(path)
         .map_err(|e| format!("Failed to read vault directory: {}", e))?;
-    
+
     for entry in entries {
         let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
         let file_path = entry.path();
-        
+
         // Only include .mschema files
         if let Some(ext) = file_path.extension() {
             if ext == "mschema" {
@@ -47,10 +71,10 @@ pub fn get_vault_files(vault_path: &str) -> Result<Vec<FileEntry>, String> {
                     .and_then(|s| s.to_str())
                     .unwrap_or("Unknown")
                     .to_string();
-                
+
                 let metadata = fs::metadata(&file_path)
                     .map_err(|e| format!("Failed to read file metadata: {}", e))?;
-                
+
                 let modified_at = metadata
                     .modified()
                     .map(|t| {
@@ -58,22 +82,163 @@ pub fn get_vault_files(vault_path: &str) -> Result<Vec<FileEntry>, String> {
                         datetime.to_rfc3339()
                     })
                     .unwrap_or_else(|_| String::from("Unknown"));
-                
+
                 files.push(FileEntry {
                     name,
                     path: file_path.to_string_lossy().to_string(),
                     modified_at,
+                    locked,
                 });
             }
         }
     }
-    
+
     // Sort by modified date, newest first
     files.sort_by(|a, b| b.modified_at.cmp(&a.modified_at));
-    
+
     Ok(files)
 }
 
+// -----------------------------------------------------------------------------
+// Encrypted Vaults
+// -----------------------------------------------------------------------------
+
+/// Turn a vault directory into an encrypted vault protected by `passphrase`.
+/// Fails if the directory is already an encrypted vault.
+#[tauri::command]
+pub fn create_encrypted_vault(
+    app_handle: tauri::AppHandle,
+    path: &str,
+    passphrase: &str,
+    state: tauri::State<VaultKeyStore>,
+) -> Result<(), String> {
+    let vault_dir = resolve_within_vaults(&app_handle, path)?;
+    let vault_dir = vault_dir.as_path();
+
+    if !vault_dir.is_dir() {
+        return Err(format!("Invalid vault path: {}", path));
+    }
+
+    if is_encrypted_vault(vault_dir) {
+        return Err("Vault is already encrypted".to_string());
+    }
+
+    let (metadata, vault_key) = create_vault_metadata(passphrase)?;
+    encrypt_existing_vault_files(vault_dir, &vault_key)?;
+    write_vault_metadata(vault_dir, &metadata)?;
+
+    let canonical = canonical_key(vault_dir)?;
+    state
+        .0
+        .lock()
+        .map_err(|_| "Vault key store is poisoned".to_string())?
+        .insert(canonical, vault_key);
+
+    Ok(())
+}
+
+/// Unlock an encrypted vault with `passphrase`, holding the derived vault
+/// key in app state for subsequent file reads/writes.
+#[tauri::command]
+pub fn unlock_vault(
+    app_handle: tauri::AppHandle,
+    path: &str,
+    passphrase: &str,
+    state: tauri::State<VaultKeyStore>,
+) -> Result<(), String> {
+    let vault_dir = resolve_within_vaults(&app_handle, path)?;
+    let vault_dir = vault_dir.as_path();
+    let metadata = read_vault_metadata(vault_dir)?;
+    let vault_key = unwrap_vault_key(&metadata, passphrase)?;
+
+    let canonical = canonical_key(vault_dir)?;
+    state
+        .0
+        .lock()
+        .map_err(|_| "Vault key store is poisoned".to_string())?
+        .insert(canonical, vault_key);
+
+    Ok(())
+}
+
+/// Change the passphrase protecting an encrypted vault, given its current
+/// passphrase; the vault need not be unlocked first. The underlying vault
+/// key is kept, so existing encrypted files stay valid.
+#[tauri::command]
+pub fn change_vault_passphrase(
+    app_handle: tauri::AppHandle,
+    path: &str,
+    current_passphrase: &str,
+    new_passphrase: &str,
+) -> Result<(), String> {
+    let vault_dir = resolve_within_vaults(&app_handle, path)?;
+    let vault_dir = vault_dir.as_path();
+    let metadata = read_vault_metadata(vault_dir)?;
+    let vault_key = unwrap_vault_key(&metadata, current_passphrase)?;
+
+    // Re-wrap the same vault key under the new passphrase so files already
+    // encrypted under the old one remain readable.
+    let new_metadata = crypto::wrap_vault_key(&vault_key, new_passphrase)?;
+    write_vault_metadata(vault_dir, &new_metadata)?;
+    Ok(())
+}
+
+/// Encrypt every pre-existing `.mschema` file in `vault_dir` in place under
+/// `vault_key`, so notes written before the vault was locked stay readable
+/// afterwards instead of becoming silently undecryptable.
+fn encrypt_existing_vault_files(vault_dir: &Path, vault_key: &VaultKey) -> Result<(), String> {
+    let entries = fs::read_dir(vault_dir)
+        .map_err(|e| format!("Failed to read vault directory: {}", e))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let file_path = entry.path();
+
+        if file_path.extension().map(|ext| ext == "mschema") != Some(true) {
+            continue;
+        }
+
+        let plaintext = fs::read(&file_path)
+            .map_err(|e| format!("Failed to read {}: {}", file_path.display(), e))?;
+        let ciphertext = crypto::encrypt_file_body(vault_key, &plaintext)?;
+        atomic_write(&file_path, &ciphertext)?;
+    }
+
+    Ok(())
+}
+
+fn is_vault_unlocked(vault_dir: &Path, state: &tauri::State<VaultKeyStore>) -> bool {
+    match canonical_key(vault_dir) {
+        Ok(key) => state
+            .0
+            .lock()
+            .map(|map| map.contains_key(&key))
+            .unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+fn canonical_key(vault_dir: &Path) -> Result<String, String> {
+    let canonical = vault_dir
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve vault path: {}", e))?;
+    Ok(canonical.to_string_lossy().to_string())
+}
+
+fn read_vault_metadata(vault_dir: &Path) -> Result<VaultMetadata, String> {
+    let metadata_path = vault_metadata_path(vault_dir);
+    let content = fs::read_to_string(&metadata_path)
+        .map_err(|e| format!("Failed to read vault.json: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse vault.json: {}", e))
+}
+
+fn write_vault_metadata(vault_dir: &Path, metadata: &VaultMetadata) -> Result<(), String> {
+    let metadata_path = vault_metadata_path(vault_dir);
+    let content = serde_json::to_string_pretty(metadata)
+        .map_err(|e| format!("Failed to serialize vault.json: {}", e))?;
+    atomic_write(&metadata_path, content.as_bytes())
+}
+
 /// Get the saved vault path from app data
 #[tauri::command]
 pub fn get_saved_vault_path(app_handle: tauri::AppHandle) -> Result<Option<String>, String> {
@@ -11,6 +11,10 @@ use commands::{
     get_saved_vault_path,
     save_vault_path,
     clear_saved_vault_path,
+    create_encrypted_vault,
+    unlock_vault,
+    change_vault_passphrase,
+    VaultKeyStore,
     // File commands
     read_map_file,
     write_map_file,
@@ -29,12 +33,16 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
+        .manage(VaultKeyStore::default())
         .invoke_handler(tauri::generate_handler![
             // Vault commands
             get_vault_files,
             get_saved_vault_path,
             save_vault_path,
             clear_saved_vault_path,
+            create_encrypted_vault,
+            unlock_vault,
+            change_vault_passphrase,
             // File commands
             read_map_file,
             write_map_file,